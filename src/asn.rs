@@ -0,0 +1,239 @@
+//! ASN -> prefix resolution. `api.bgpview.io` is the default backend, but it
+//! rate-limits and occasionally goes down, so `--asn-source` can pick RIPEstat
+//! instead, and `auto` (the default) falls back to it automatically when the
+//! primary errors or returns no prefixes. Results are cached on disk, keyed
+//! by ASN, so repeated scans of the same AS don't re-hit the network.
+
+use anyhow::{anyhow, Result};
+use ipnet::IpNet;
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use std::os::unix::fs::{MetadataExt, OpenOptionsExt, PermissionsExt};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Backend for `--asn-source`. `Auto` tries bgpview first and falls back to
+/// RIPEstat if it errors or comes back with zero prefixes.
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AsnSource {
+    Auto,
+    Bgpview,
+    Ripestat,
+}
+
+/// Resolves an AS number (`AS15169` or bare `15169`) to its announced
+/// prefixes. Checks the on-disk cache first; on a miss, queries backends in
+/// the order implied by `source` and caches the first non-empty result.
+pub async fn resolve_asn_prefixes(
+    asn_input: &str,
+    source: AsnSource,
+    cache_ttl: Duration,
+) -> Result<Vec<IpNet>> {
+    let asn = normalize_asn(asn_input)?;
+
+    if let Some(cached) = read_cache(&asn, source, cache_ttl) {
+        return Ok(cached);
+    }
+
+    let backends: &[AsnSource] = match source {
+        AsnSource::Bgpview => &[AsnSource::Bgpview],
+        AsnSource::Ripestat => &[AsnSource::Ripestat],
+        AsnSource::Auto => &[AsnSource::Bgpview, AsnSource::Ripestat],
+    };
+
+    let mut last_err = None;
+    for backend in backends {
+        let attempt = match backend {
+            AsnSource::Bgpview => fetch_bgpview(&asn).await,
+            AsnSource::Ripestat => fetch_ripestat(&asn).await,
+            AsnSource::Auto => unreachable!("Auto is expanded before dispatch"),
+        };
+        match attempt {
+            Ok(prefixes) if !prefixes.is_empty() => {
+                if !cache_ttl.is_zero() {
+                    write_cache(&asn, source, &prefixes);
+                }
+                return Ok(prefixes);
+            }
+            Ok(_) => continue,
+            Err(e) => last_err = Some(e),
+        }
+    }
+    Err(last_err.unwrap_or_else(|| anyhow!("no prefixes found for {}", asn)))
+}
+
+/// Normalizes to `AS<digits>`, rejecting anything else so a malformed or
+/// hostile `--asn` value (e.g. containing `/` or `..`) can't end up in the
+/// cache file path built from this string.
+fn normalize_asn(input: &str) -> Result<String> {
+    let trimmed = input.trim().to_uppercase();
+    let digits = trimmed.strip_prefix("AS").unwrap_or(&trimmed);
+    if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+        return Err(anyhow!("invalid ASN: {}", input));
+    }
+    Ok(format!("AS{}", digits))
+}
+
+/* ---------- bgpview backend ---------- */
+
+#[derive(Deserialize)]
+struct BgpviewPrefixes {
+    data: BgpviewData,
+}
+#[derive(Deserialize)]
+struct BgpviewData {
+    ipv4_prefixes: Vec<PrefixEntry>,
+    ipv6_prefixes: Vec<PrefixEntry>,
+}
+#[derive(Deserialize)]
+struct PrefixEntry {
+    prefix: String,
+}
+
+async fn fetch_bgpview(asn: &str) -> Result<Vec<IpNet>> {
+    let url = format!("https://api.bgpview.io/asn/{}/prefixes", asn);
+    let client = reqwest::Client::builder().user_agent("webfigchecker/1.3").build()?;
+    let resp: BgpviewPrefixes = client
+        .get(url)
+        .header(reqwest::header::ACCEPT, "application/json")
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+
+    let mut out = Vec::new();
+    for e in resp.data.ipv4_prefixes {
+        if let Ok(net) = e.prefix.parse::<IpNet>() {
+            if net.addr().is_ipv4() {
+                out.push(net);
+            }
+        }
+    }
+    for e in resp.data.ipv6_prefixes {
+        if let Ok(net) = e.prefix.parse::<IpNet>() {
+            if net.addr().is_ipv6() {
+                out.push(net);
+            }
+        }
+    }
+    Ok(out)
+}
+
+/* ---------- RIPEstat backend ---------- */
+
+#[derive(Deserialize)]
+struct RipestatResponse {
+    data: RipestatData,
+}
+#[derive(Deserialize)]
+struct RipestatData {
+    prefixes: Vec<RipestatPrefixEntry>,
+}
+#[derive(Deserialize)]
+struct RipestatPrefixEntry {
+    prefix: String,
+}
+
+/// https://stat.ripe.net/docs/02.data-api/announced-prefixes.html
+async fn fetch_ripestat(asn: &str) -> Result<Vec<IpNet>> {
+    let number = asn.trim_start_matches("AS");
+    let url = format!(
+        "https://stat.ripe.net/data/announced-prefixes/data.json?resource=AS{}",
+        number
+    );
+    let client = reqwest::Client::builder().user_agent("webfigchecker/1.3").build()?;
+    let resp: RipestatResponse = client.get(url).send().await?.error_for_status()?.json().await?;
+
+    Ok(resp
+        .data
+        .prefixes
+        .into_iter()
+        .filter_map(|e| e.prefix.parse::<IpNet>().ok())
+        .collect())
+}
+
+/* ---------- on-disk cache ---------- */
+
+#[derive(Serialize, Deserialize)]
+struct CacheEntry {
+    fetched_at: u64,
+    prefixes: Vec<String>,
+}
+
+/// Per-user cache root (`$XDG_CACHE_HOME/webfigchecker/asn` or
+/// `$HOME/.cache/webfigchecker/asn`), never the shared, world-writable temp
+/// dir — another local user can't plant or swap cache entries for ASNs this
+/// process hasn't looked up yet.
+fn cache_dir() -> Option<PathBuf> {
+    let base = std::env::var_os("XDG_CACHE_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|h| PathBuf::from(h).join(".cache")))?;
+    Some(base.join("webfigchecker").join("asn"))
+}
+
+/// Creates the cache directory locked to the current user (`0700`): refuses
+/// to use it if it isn't owned by us, and tightens it to `0700` if it is ours
+/// but more permissive than that — guards against a pre-existing directory
+/// planted by another user turning a later write into a symlink-following
+/// overwrite.
+fn ensure_cache_dir(dir: &Path) -> Result<()> {
+    std::fs::create_dir_all(dir)?;
+    let meta = std::fs::symlink_metadata(dir)?;
+    let our_uid = unsafe { libc::geteuid() };
+    if meta.uid() != our_uid {
+        return Err(anyhow!("refusing to use asn cache dir not owned by us: {}", dir.display()));
+    }
+    if meta.permissions().mode() & 0o077 != 0 {
+        std::fs::set_permissions(dir, std::fs::Permissions::from_mode(0o700))?;
+    }
+    Ok(())
+}
+
+/// `asn` is already validated as `AS<digits>` by `normalize_asn`, so it's
+/// safe to use directly as a path component here.
+fn cache_path(dir: &Path, asn: &str, source: AsnSource) -> PathBuf {
+    let tag = match source {
+        AsnSource::Auto => "auto",
+        AsnSource::Bgpview => "bgpview",
+        AsnSource::Ripestat => "ripestat",
+    };
+    dir.join(format!("{}-{}.json", asn, tag))
+}
+
+fn read_cache(asn: &str, source: AsnSource, ttl: Duration) -> Option<Vec<IpNet>> {
+    if ttl.is_zero() {
+        return None;
+    }
+    let dir = cache_dir()?;
+    ensure_cache_dir(&dir).ok()?;
+    let bytes = std::fs::read(cache_path(&dir, asn, source)).ok()?;
+    let entry: CacheEntry = serde_json::from_slice(&bytes).ok()?;
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).ok()?.as_secs();
+    if now.saturating_sub(entry.fetched_at) > ttl.as_secs() {
+        return None;
+    }
+    entry.prefixes.iter().map(|p| p.parse::<IpNet>().ok()).collect()
+}
+
+fn write_cache(asn: &str, source: AsnSource, prefixes: &[IpNet]) {
+    let Some(dir) = cache_dir() else { return };
+    if ensure_cache_dir(&dir).is_err() {
+        return;
+    }
+    let path = cache_path(&dir, asn, source);
+    let entry = CacheEntry {
+        fetched_at: SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0),
+        prefixes: prefixes.iter().map(IpNet::to_string).collect(),
+    };
+    let Ok(bytes) = serde_json::to_vec(&entry) else { return };
+    if let Ok(file) = std::fs::OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .mode(0o600)
+        .open(&path)
+    {
+        let _ = (&file).write_all(&bytes);
+    }
+}
@@ -0,0 +1,34 @@
+//! systemd integration for `--daemon` mode: readiness/status notifications
+//! and watchdog keepalive pings via the sd_notify protocol.
+
+use sd_notify::NotifyState;
+use std::time::Duration;
+use tokio::task::JoinHandle;
+
+/// Tells systemd the service has finished starting up (`Type=notify` units).
+pub fn notify_ready() {
+    let _ = sd_notify::notify(false, &[NotifyState::Ready]);
+}
+
+/// Updates the one-line status systemd shows for `systemctl status`.
+pub fn notify_status(msg: &str) {
+    let _ = sd_notify::notify(false, &[NotifyState::Status(msg)]);
+}
+
+/// If the unit declares `WatchdogSec=`, systemd exports `WATCHDOG_USEC` and
+/// expects a `WATCHDOG=1` ping at least that often or it restarts the
+/// service. Pings at half that interval to leave headroom for a slow pass.
+/// Returns `None` (no task spawned) when no watchdog is configured.
+pub fn spawn_watchdog() -> Option<JoinHandle<()>> {
+    let usec: u64 = std::env::var("WATCHDOG_USEC").ok()?.parse().ok()?;
+    if usec == 0 {
+        return None;
+    }
+    let period = Duration::from_micros(usec / 2);
+    Some(tokio::spawn(async move {
+        loop {
+            tokio::time::sleep(period).await;
+            let _ = sd_notify::notify(false, &[NotifyState::Watchdog]);
+        }
+    }))
+}
@@ -1,24 +1,33 @@
 use anyhow::{anyhow, Result};
 use clap::Parser;
+use futures::stream::{self, StreamExt};
 use ipnet::IpNet;
 use regex::Regex;
-use serde::Deserialize;
-use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::collections::HashSet;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::path::PathBuf;
 use std::str::FromStr;
-use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex as StdMutex};
 use std::time::Duration;
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
 use tokio::net::TcpStream;
-use tokio::sync::Semaphore;
+use tokio::sync::Mutex as AsyncMutex;
 use tokio::time::timeout;
 
+mod asn;
+mod daemon;
+mod output;
+mod targets;
+
 #[derive(Parser, Debug)]
 #[command(
     name = "webfigchecker",
     about = "Scan IP/ASN/CIDR/range to detect MikroTik WebFig"
 )]
 struct Args {
-    /// Single IP (ignored if --asn/--cidr/--ip-range used)
+    /// Single IP or hostname (ignored if --asn/--cidr/--ip-range/
+    /// --targets-file/--url is used)
     #[arg(required = false)]
     ip: Option<String>,
 
@@ -26,11 +35,22 @@ struct Args {
     #[arg(long)]
     asn: Option<String>,
 
-    /// CIDR network to expand, e.g. 192.168.1.0/24
+    /// Backend for --asn prefix lookups. `auto` tries bgpview first and
+    /// falls back to RIPEstat if it errors or returns nothing
+    #[arg(long = "asn-source", value_enum, default_value = "auto")]
+    asn_source: asn::AsnSource,
+
+    /// How long cached --asn prefix results stay valid, in seconds (0 disables
+    /// the cache)
+    #[arg(long = "asn-cache-ttl", default_value_t = 3600)]
+    asn_cache_ttl: u64,
+
+    /// CIDR network to expand, e.g. 192.168.1.0/24 or 2001:db8::/32
     #[arg(long)]
     cidr: Option<String>,
 
-    /// IPv4 range: start-end, e.g. 192.168.1.10-192.168.1.50 or 192.168.1.10-50
+    /// Range: start-end, e.g. 192.168.1.10-192.168.1.50, 192.168.1.10-50,
+    /// or 2001:db8::1-2001:db8::ff
     #[arg(long = "ip-range")]
     ip_range: Option<String>,
 
@@ -46,11 +66,13 @@ struct Args {
     #[arg(long)]
     all_ports: bool,
 
-    /// For ASN/CIDR: sample N IPs per prefix (default 1)
+    /// For ASN/CIDR: sample N IPs per prefix (default 1), IPv4 and IPv6
+    /// alike, unless --expand-all-ips is set.
     #[arg(long, default_value_t = 1)]
     per_prefix: usize,
 
-    /// EXPENSIVE: expand every IP in every prefix for ASN/CIDR
+    /// EXPENSIVE: expand every IP in every prefix for ASN/CIDR. IPv6 prefixes
+    /// are still capped (see IPV6_EXPAND_CAP) since a /64 can't be enumerated.
     #[arg(long)]
     expand_all_ips: bool,
 
@@ -61,6 +83,42 @@ struct Args {
     /// Per-connection timeout (ms)
     #[arg(long = "timeout-ms", default_value_t = 800)]
     timeout_ms: u64,
+
+    /// Run as a long-lived systemd-style daemon, re-scanning every --interval
+    /// seconds instead of exiting after one pass
+    #[arg(long)]
+    daemon: bool,
+
+    /// Seconds between scan passes when --daemon is set
+    #[arg(long, default_value_t = 300)]
+    interval: u64,
+
+    /// Append newline-delimited JSON detection records to this file
+    #[arg(long)]
+    output: Option<PathBuf>,
+
+    /// Print detected hosts as `nft -f` add-element lines for the named set,
+    /// on stdout after the scan finishes. Stdout also carries the banner and
+    /// the per-host result lines, so it is not a loadable script as a whole.
+    #[arg(long = "export-nftables", value_name = "SET")]
+    export_nftables: Option<String>,
+
+    /// Print detected hosts as `ipset restore` add lines for the named set,
+    /// on stdout after the scan finishes. Stdout also carries the banner and
+    /// the per-host result lines, so it is not loadable as a whole.
+    #[arg(long = "export-ipset", value_name = "SET")]
+    export_ipset: Option<String>,
+
+    /// Read targets from a file, one per line: IPs, CIDRs, ranges, or
+    /// hostnames. Blank lines and `#` comments are skipped.
+    #[arg(long = "targets-file", value_name = "PATH")]
+    targets_file: Option<PathBuf>,
+
+    /// Scan a single `scheme://host[:port]` URL. The host may be an IP or a
+    /// hostname; the port defaults from the scheme unless --port/--ports/
+    /// --all-ports is also given.
+    #[arg(long)]
+    url: Option<String>,
 }
 
 
@@ -76,115 +134,176 @@ fn print_banner() {
 async fn main() -> Result<()> {
     print_banner();
     let args = Args::parse();
+
+    if args.daemon {
+        return run_daemon(args).await;
+    }
+
+    scan_once(&args).await?;
+    Ok(())
+}
+
+/// Keeps the process alive as a systemd service: signals readiness, pings
+/// the watchdog if the unit declares one, and re-runs `scan_once` every
+/// `--interval` seconds instead of exiting after a single pass.
+async fn run_daemon(args: Args) -> Result<()> {
+    daemon::notify_ready();
+    let _watchdog = daemon::spawn_watchdog();
+    let interval = Duration::from_secs(args.interval);
+
+    loop {
+        daemon::notify_status("scanning");
+        match scan_once(&args).await {
+            Ok(found) => daemon::notify_status(&format!(
+                "idle, last pass found {} WebFig host(s), next pass in {}s",
+                found, args.interval
+            )),
+            Err(e) => {
+                eprintln!("scan pass failed: {:#}", e);
+                daemon::notify_status(&format!(
+                    "idle, last pass failed ({}), next pass in {}s",
+                    e, args.interval
+                ));
+            }
+        }
+        tokio::time::sleep(interval).await;
+    }
+}
+
+/// Builds the target/port pipeline from `args` and runs it to completion
+/// once, returning how many WebFig hosts were found.
+async fn scan_once(args: &Args) -> Result<usize> {
     let timeout_dur = Duration::from_millis(args.timeout_ms);
-    let portset = build_portset(&args)?;
 
-    // Build list of target IPs
-    let targets: Vec<IpAddr> = if let Some(asn) = args.asn.as_deref() {
-        let prefixes = fetch_asn_prefixes(asn).await?;
-        eprintln!("ASN {} -> {} prefixes", asn, prefixes.len());
-        expand_prefixes(&prefixes, args.per_prefix, args.expand_all_ips)?
+    // Build a *lazy* stream of target IPs so --all-ports/--expand-all-ips scans
+    // never materialize the whole target list up front. The file/URL/single-
+    // target branches resolve through `targets` (hostnames need an async DNS
+    // lookup) and are small enough to collect into a deduplicated `Vec` first.
+    let mut url_port = None;
+    let ip_iter: Box<dyn Iterator<Item = IpAddr>> = if let Some(asn_input) = args.asn.as_deref() {
+        let cache_ttl = Duration::from_secs(args.asn_cache_ttl);
+        let prefixes = asn::resolve_asn_prefixes(asn_input, args.asn_source, cache_ttl).await?;
+        eprintln!("ASN {} -> {} prefixes", asn_input, prefixes.len());
+        Box::new(expand_prefixes(prefixes, args.per_prefix, args.expand_all_ips))
     } else if let Some(c) = args.cidr.as_deref() {
         let net: IpNet = c.parse()?;
-        expand_prefixes(&[net], args.per_prefix, args.expand_all_ips)?
+        Box::new(expand_prefixes(vec![net], args.per_prefix, args.expand_all_ips))
     } else if let Some(r) = args.ip_range.as_deref() {
-        expand_ipv4_range(r)?
+        if r.contains(':') {
+            Box::new(expand_ipv6_range(r)?)
+        } else {
+            Box::new(expand_ipv4_range(r)?)
+        }
+    } else if let Some(path) = args.targets_file.as_deref() {
+        let ips = targets::read_targets_file(path, args.per_prefix, args.expand_all_ips).await?;
+        eprintln!("{} -> {} target(s)", path.display(), ips.len());
+        Box::new(ips.into_iter())
+    } else if let Some(url) = args.url.as_deref() {
+        let (host, port) = targets::parse_url(url)?;
+        url_port = Some(port);
+        let ips = targets::dedup(targets::resolve_target(&host, args.per_prefix, args.expand_all_ips).await?);
+        Box::new(ips.into_iter())
     } else if let Some(ip) = args.ip.as_deref() {
-        vec![ip.parse()?]
+        let ips = targets::dedup(targets::resolve_target(ip, args.per_prefix, args.expand_all_ips).await?);
+        Box::new(ips.into_iter())
     } else {
         return Err(anyhow!(
-            "Give one of: <IP> | --asn AS12345 | --cidr NET | --ip-range A-B"
+            "Give one of: <IP> | --asn AS12345 | --cidr NET | --ip-range A-B | --targets-file PATH | --url scheme://host"
         ));
     };
 
+    let portset = Arc::new(build_portset(args, url_port)?);
+
     eprintln!(
-        "Targets: {} | Ports: {} | concurrency={} | timeout={}ms",
-        targets.len(),
+        "Ports: {} | concurrency={} | timeout={}ms",
         portset.len(),
         args.concurrency,
         args.timeout_ms
     );
 
-    let sem =  Arc::new(Semaphore::new(args.concurrency));
-    let mut tasks = Vec::new();
-
-    for ip in targets {
-        for &port in &portset {
-            let sem = sem.clone();
-            let to = timeout_dur;
-            tasks.push(tokio::spawn(async move {
-                let _p = sem.acquire().await.unwrap();
-                if let Ok(Some(prod)) = check_webfig(ip, port, to).await {
-                    println!("{}:{} -> {}", ip, port, prod);
+    let pairs = ip_iter.flat_map(move |ip| {
+        let portset = Arc::clone(&portset);
+        (0..portset.len()).map(move |i| (ip, portset[i]))
+    });
+
+    let processed = Arc::new(AtomicUsize::new(0));
+    let found = Arc::new(AtomicUsize::new(0));
+    let daemon_mode = args.daemon;
+
+    let jsonl = match &args.output {
+        Some(path) => Some(Arc::new(AsyncMutex::new(output::JsonlWriter::create(path).await?))),
+        None => None,
+    };
+    let track_hosts = args.export_nftables.is_some() || args.export_ipset.is_some();
+    let detected_hosts = Arc::new(StdMutex::new(HashSet::new()));
+
+    stream::iter(pairs)
+        .map(|(ip, port)| async move { check_webfig(ip, port, timeout_dur).await })
+        .buffer_unordered(args.concurrency)
+        .for_each(|res| {
+            let processed = Arc::clone(&processed);
+            let found = Arc::clone(&found);
+            let jsonl = jsonl.clone();
+            let detected_hosts = Arc::clone(&detected_hosts);
+            async move {
+                let n = processed.fetch_add(1, Ordering::Relaxed) + 1;
+                if let Ok(Some(det)) = res {
+                    found.fetch_add(1, Ordering::Relaxed);
+                    println!("{}:{} -> {}", det.ip, det.port, det.product);
+                    if track_hosts {
+                        detected_hosts.lock().unwrap().insert(det.ip);
+                    }
+                    if let Some(writer) = &jsonl {
+                        let _ = writer.lock().await.write(&det).await;
+                    }
                 }
-            }));
-        }
+                if daemon_mode && n.is_multiple_of(100) {
+                    daemon::notify_status(&format!(
+                        "scanning, {} checked so far, {} WebFig found",
+                        n,
+                        found.load(Ordering::Relaxed)
+                    ));
+                }
+            }
+        })
+        .await;
+
+    if let Some(set) = &args.export_nftables {
+        let hosts = detected_hosts.lock().unwrap();
+        output::write_nftables_set(&mut std::io::stdout(), set, &hosts)?;
+    }
+    if let Some(set) = &args.export_ipset {
+        let hosts = detected_hosts.lock().unwrap();
+        output::write_ipset(&mut std::io::stdout(), set, &hosts)?;
     }
 
-    for t in tasks { let _ = t.await; }
-    Ok(())
+    Ok(found.load(Ordering::Relaxed))
 }
 
 /* ---------- targets helpers ---------- */
 
-#[derive(Deserialize)]
-struct BgpviewPrefixes { data: BgpviewData }
-#[derive(Deserialize)]
-struct BgpviewData {
-    ipv4_prefixes: Vec<PrefixEntry>,
-    #[allow(dead_code)]
-    ipv6_prefixes: Vec<PrefixEntry>,
-}
-#[derive(Deserialize)]
-struct PrefixEntry { prefix: String }
-
-async fn fetch_asn_prefixes(asn_input: &str) -> Result<Vec<IpNet>> {
-    // Must be like "AS12345"
-    let mut asn = asn_input.trim().to_uppercase();
-    if !asn.starts_with("AS") { asn = format!("AS{}", asn); }
-    let url = format!("https://api.bgpview.io/asn/{}/prefixes", asn);
-
-    let client = reqwest::Client::builder()
-        .user_agent("webfigchecker/1.3")
-        .build()?;
-
-    let resp: BgpviewPrefixes = client
-        .get(url)
-        .header(reqwest::header::ACCEPT, "application/json")
-        .send()
-        .await?
-        .error_for_status()?
-        .json()
-        .await?;
-
-    let mut out = Vec::new();
-    for e in resp.data.ipv4_prefixes {
-        if let Ok(net) = e.prefix.parse::<IpNet>() {
-            if net.addr().is_ipv4() { out.push(net); }
+/// Cap on how many hosts an IPv6 prefix expands to, even under
+/// `--expand-all-ips` — a /64 has 2^64 addresses, so "expand all" still
+/// needs a ceiling or it never finishes. IPv4 has no such cap since even a
+/// /8 (16M hosts) is a workable, finite scan.
+const IPV6_EXPAND_CAP: usize = 65_536;
+
+/// Lazily expands a set of prefixes into host addresses. `prefixes` is taken
+/// by value so the returned iterator owns its data and stays `'static`,
+/// which lets callers box it alongside the other target-expansion branches
+/// without ever collecting into a `Vec`.
+pub(crate) fn expand_prefixes(prefixes: Vec<IpNet>, per: usize, all: bool) -> impl Iterator<Item = IpAddr> {
+    let v4_take = if all { usize::MAX } else { per };
+    let v6_take = if all { IPV6_EXPAND_CAP } else { per };
+    prefixes.into_iter().flat_map(move |p| -> Box<dyn Iterator<Item = IpAddr>> {
+        match p {
+            IpNet::V4(v4) => Box::new(v4.hosts().take(v4_take).map(IpAddr::V4)),
+            IpNet::V6(v6) => Box::new(v6.hosts().take(v6_take).map(IpAddr::V6)),
         }
-    }
-    Ok(out)
-}
-
-fn expand_prefixes(prefixes: &[IpNet], per: usize, all: bool) -> Result<Vec<IpAddr>> {
-    let mut ips = Vec::new();
-    for p in prefixes {
-        if let IpNet::V4(v4) = p {
-            if all {
-                for ip in v4.hosts() { ips.push(IpAddr::V4(ip)); }
-            } else {
-                let mut n = 0usize;
-                for ip in v4.hosts() {
-                    ips.push(IpAddr::V4(ip));
-                    n += 1; if n >= per { break; }
-                }
-            }
-        }
-    }
-    Ok(ips)
+    })
 }
 
-fn expand_ipv4_range(spec: &str) -> Result<Vec<IpAddr>> {
+pub(crate) fn expand_ipv4_range(spec: &str) -> Result<impl Iterator<Item = IpAddr>> {
     let parts: Vec<&str> = spec.split('-').collect();
     if parts.len() != 2 {
         return Err(anyhow!("ip-range must be like 192.168.1.10-192.168.1.50 or 192.168.1.10-50"));
@@ -198,15 +317,36 @@ fn expand_ipv4_range(spec: &str) -> Result<Vec<IpAddr>> {
         Ipv4Addr::from_str(&s.join("."))?
     };
     if u32::from(start) > u32::from(end) { return Err(anyhow!("start > end")); }
-    let mut v = Vec::new();
-    let (mut a, b) = (u32::from(start), u32::from(end));
-    while a <= b { v.push(IpAddr::V4(Ipv4Addr::from(a))); a += 1; }
-    Ok(v)
+    let (a, b) = (u32::from(start), u32::from(end));
+    Ok((a..=b).map(Ipv4Addr::from).map(IpAddr::V4))
+}
+
+/// IPv6 counterpart of `expand_ipv4_range`: `start-end`, e.g. `2001:db8::1-2001:db8::ff`.
+/// Spans larger than `IPV6_EXPAND_CAP` are truncated rather than enumerated in full.
+pub(crate) fn expand_ipv6_range(spec: &str) -> Result<impl Iterator<Item = IpAddr>> {
+    let (start_str, end_str) = spec
+        .split_once('-')
+        .ok_or_else(|| anyhow!("ipv6 ip-range must be like 2001:db8::1-2001:db8::ff"))?;
+    let start: Ipv6Addr = start_str.parse()?;
+    let end: Ipv6Addr = end_str.parse()?;
+    let (a, b) = (u128::from(start), u128::from(end));
+    if a > b { return Err(anyhow!("start > end")); }
+    let span = (b - a).min(IPV6_EXPAND_CAP as u128 - 1);
+    if b - a > span {
+        eprintln!(
+            "ipv6 range has {} addresses, capping at {}",
+            b - a + 1,
+            IPV6_EXPAND_CAP
+        );
+    }
+    Ok((0..=span).map(move |i| IpAddr::V6(Ipv6Addr::from(a + i))))
 }
 
 /* ---------- ports helpers ---------- */
 
-fn build_portset(a: &Args) -> Result<Vec<u16>> {
+/// `url_port` is the port parsed out of `--url`, if any; it's used only when
+/// none of --all-ports/--ports/--port were given explicitly.
+fn build_portset(a: &Args, url_port: Option<u16>) -> Result<Vec<u16>> {
     if a.all_ports { return Ok((1u16..=65535u16).collect()); }
     if let Some(s) = &a.ports {
         let mut v: Vec<u16> = Vec::new();
@@ -227,28 +367,26 @@ fn build_portset(a: &Args) -> Result<Vec<u16>> {
         return Ok(v);
     }
     if let Some(p) = a.port { return Ok(vec![p]); }
-    Ok(vec![80, 443, 8080, 8291])
-}
-    if let Some(s) = &a.ports {
-        let mut v = Vec::new();
-        for p in s.split(',') { v.push(p.trim().parse::<u16>()?); }
-        v.sort_unstable(); v.dedup(); return Ok(v);
-    }
-    if let Some(p) = a.port { return Ok(vec![p]); }
+    if let Some(p) = url_port { return Ok(vec![p]); }
     Ok(vec![80, 443, 8080, 8291])
 }
 
 /* ---------- detector ---------- */
 
-async fn check_webfig(ip: IpAddr, port: u16, to: Duration) -> Result<Option<String>> {
+async fn check_webfig(ip: IpAddr, port: u16, to: Duration) -> Result<Option<output::Detection>> {
     let addr = SocketAddr::new(ip, port);
     let mut stream = match timeout(to, TcpStream::connect(addr)).await {
         Ok(Ok(s)) => s, _ => return Ok(None),
     };
 
+    // IPv6 literals must be bracketed in a Host header (RFC 3986 §3.2.2).
+    let host_header = match ip {
+        IpAddr::V4(v4) => v4.to_string(),
+        IpAddr::V6(v6) => format!("[{}]", v6),
+    };
     let req = format!(
         "GET / HTTP/1.1\r\nHost: {}\r\nUser-Agent: webfigchecker/1.3\r\nConnection: close\r\n\r\n",
-        ip
+        host_header
     );
     let _ = stream.write_all(req.as_bytes()).await;
 
@@ -256,7 +394,8 @@ async fn check_webfig(ip: IpAddr, port: u16, to: Duration) -> Result<Option<Stri
     let n = match timeout(to, stream.read(&mut buf)).await {
         Ok(Ok(n)) if n > 0 => n, _ => return Ok(None),
     };
-    let body = String::from_utf8_lossy(&buf[..n]).to_lowercase();
+    let raw_body = String::from_utf8_lossy(&buf[..n]).into_owned();
+    let body = raw_body.to_lowercase();
 
     let has = body.contains("webfig") || body.contains("mikrotik") || body.contains("routeros");
     if !has {
@@ -264,7 +403,9 @@ async fn check_webfig(ip: IpAddr, port: u16, to: Duration) -> Result<Option<Stri
         if !re.is_match(&body) { return Ok(None); }
     }
     let product = extract_product(&body).unwrap_or_else(|| "WebFig".to_string());
-    Ok(Some(product))
+    let routeros_version = extract_routeros_version(&body);
+    let banner_snippet = banner_snippet(&raw_body);
+    Ok(Some(output::Detection::new(ip, port, product, routeros_version, banner_snippet)))
 }
 
 fn extract_product(s: &str) -> Option<String> {
@@ -282,3 +423,14 @@ fn extract_product(s: &str) -> Option<String> {
     }
     None
 }
+
+fn extract_routeros_version(s: &str) -> Option<String> {
+    let re = Regex::new(r"routeros\s*v?(\d+(?:\.\d+)+)").ok()?;
+    re.captures(s).map(|m| m[1].to_string())
+}
+
+/// First line of the response, truncated to a safe length for a JSONL record.
+fn banner_snippet(raw_body: &str) -> String {
+    let first_line = raw_body.lines().next().unwrap_or("");
+    first_line.chars().take(160).collect()
+}
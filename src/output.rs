@@ -0,0 +1,81 @@
+//! Structured result output: newline-delimited JSON detection records, plus
+//! firewall blocklist export in nftables/ipset load-script formats.
+
+use anyhow::Result;
+use serde::Serialize;
+use std::collections::HashSet;
+use std::io::Write;
+use std::net::IpAddr;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::fs::File;
+use tokio::io::AsyncWriteExt;
+
+/// One detected WebFig/RouterOS host, as emitted by `--output`.
+#[derive(Debug, Clone, Serialize)]
+pub struct Detection {
+    pub ip: IpAddr,
+    pub port: u16,
+    pub product: String,
+    pub routeros_version: Option<String>,
+    pub banner_snippet: String,
+    pub timestamp: u64,
+}
+
+impl Detection {
+    pub fn new(
+        ip: IpAddr,
+        port: u16,
+        product: String,
+        routeros_version: Option<String>,
+        banner_snippet: String,
+    ) -> Self {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        Self { ip, port, product, routeros_version, banner_snippet, timestamp }
+    }
+}
+
+/// Appends newline-delimited JSON detection records to a file, for
+/// programmatic consumption instead of scraping stdout.
+pub struct JsonlWriter {
+    file: File,
+}
+
+impl JsonlWriter {
+    pub async fn create(path: &Path) -> Result<Self> {
+        let file = tokio::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .await?;
+        Ok(Self { file })
+    }
+
+    pub async fn write(&mut self, det: &Detection) -> Result<()> {
+        let mut line = serde_json::to_string(det)?;
+        line.push('\n');
+        self.file.write_all(line.as_bytes()).await?;
+        Ok(())
+    }
+}
+
+/// Writes `add element inet filter <set> { ip }` lines for every detected
+/// host, ready to be loaded with `nft -f`.
+pub fn write_nftables_set<W: Write>(w: &mut W, set_name: &str, ips: &HashSet<IpAddr>) -> Result<()> {
+    for ip in ips {
+        writeln!(w, "add element inet filter {} {{ {} }}", set_name, ip)?;
+    }
+    Ok(())
+}
+
+/// Writes `add <set> <ip>` lines for every detected host, in the format
+/// `ipset restore` expects.
+pub fn write_ipset<W: Write>(w: &mut W, set_name: &str, ips: &HashSet<IpAddr>) -> Result<()> {
+    for ip in ips {
+        writeln!(w, "add {} {}", set_name, ip)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,101 @@
+//! Target resolution beyond bare IP literals: DNS hostnames, `--targets-file`
+//! lists that mix IPs/CIDRs/ranges/hostnames, and `--url` endpoints.
+
+use crate::{expand_ipv4_range, expand_ipv6_range, expand_prefixes};
+use anyhow::{anyhow, Result};
+use ipnet::IpNet;
+use std::collections::HashSet;
+use std::net::IpAddr;
+use std::path::Path;
+use tokio::net::lookup_host;
+
+/// Resolves one target spec — a bare IP, a CIDR, a `start-end` range, or a
+/// DNS hostname — into the concrete addresses it names. This is the general
+/// form of the old `ip.parse()?` single-target path.
+pub async fn resolve_target(spec: &str, per_prefix: usize, expand_all_ips: bool) -> Result<Vec<IpAddr>> {
+    let spec = spec.trim();
+    if let Ok(ip) = spec.parse::<IpAddr>() {
+        return Ok(vec![ip]);
+    }
+    if let Ok(net) = spec.parse::<IpNet>() {
+        return Ok(expand_prefixes(vec![net], per_prefix, expand_all_ips).collect());
+    }
+    if spec.contains('-') {
+        if spec.contains(':') {
+            if let Ok(it) = expand_ipv6_range(spec) {
+                return Ok(it.collect());
+            }
+        } else if let Ok(it) = expand_ipv4_range(spec) {
+            return Ok(it.collect());
+        }
+    }
+    resolve_hostname(spec).await
+}
+
+/// Resolves a DNS hostname to every address (A/AAAA) it returns.
+pub async fn resolve_hostname(host: &str) -> Result<Vec<IpAddr>> {
+    let addrs = lookup_host((host, 0))
+        .await
+        .map_err(|e| anyhow!("failed to resolve {}: {}", host, e))?;
+    Ok(addrs.map(|a| a.ip()).collect())
+}
+
+/// Reads `--targets-file`: one target per line, mixing IPs, CIDRs, ranges,
+/// and hostnames. Blank lines and `#`-prefixed comments are skipped. A line
+/// that fails to resolve (e.g. a typo'd hostname) is warned about and
+/// skipped rather than aborting the rest of the file. The combined address
+/// list is deduplicated before it's returned.
+pub async fn read_targets_file(path: &Path, per_prefix: usize, expand_all_ips: bool) -> Result<Vec<IpAddr>> {
+    let content = tokio::fs::read_to_string(path).await?;
+    let mut out = Vec::new();
+    for line in content.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        match resolve_target(line, per_prefix, expand_all_ips).await {
+            Ok(ips) => out.extend(ips),
+            Err(e) => eprintln!("skipping {} targets-file line {:?}: {}", path.display(), line, e),
+        }
+    }
+    Ok(dedup(out))
+}
+
+/// Parses `scheme://host[:port]`, defaulting the port from the scheme
+/// (`http` -> 80, `https` -> 443) when the URL omits it.
+pub fn parse_url(spec: &str) -> Result<(String, u16)> {
+    let (scheme, rest) = spec
+        .split_once("://")
+        .ok_or_else(|| anyhow!("--url must be like http://host:port"))?;
+    let default_port = match scheme {
+        "http" => 80,
+        "https" => 443,
+        other => return Err(anyhow!("unsupported URL scheme: {}", other)),
+    };
+    let rest = rest.split(['/', '?', '#']).next().unwrap_or(rest);
+
+    let (host, port) = if let Some(bracketed) = rest.strip_prefix('[') {
+        let (host, after) = bracketed
+            .split_once(']')
+            .ok_or_else(|| anyhow!("unterminated IPv6 literal in --url"))?;
+        let port = after
+            .strip_prefix(':')
+            .map(str::parse)
+            .transpose()?
+            .unwrap_or(default_port);
+        (host.to_string(), port)
+    } else {
+        match rest.rsplit_once(':') {
+            Some((host, port)) if !host.is_empty() => (host.to_string(), port.parse()?),
+            _ => (rest.to_string(), default_port),
+        }
+    };
+    Ok((host, port))
+}
+
+/// Drops duplicate addresses while preserving first-seen order.
+pub fn dedup(mut ips: Vec<IpAddr>) -> Vec<IpAddr> {
+    let mut seen = HashSet::new();
+    ips.retain(|ip| seen.insert(*ip));
+    ips
+}